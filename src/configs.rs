@@ -3,8 +3,11 @@ use std::env::var;
 use std::fs::read_to_string;
 use std::path::PathBuf;
 
+use crate::providers::{self, RefreshableConfiguration};
+
 const CRED_KEY: &str = "aws_access_key_id";
 const CRED_SECRET: &str = "aws_secret_access_key";
+const CRED_TOKEN: &str = "aws_session_token";
 const CONF_REGION: &str = "region";
 
 #[derive(Debug)]
@@ -12,6 +15,7 @@ pub struct Configuration {
   pub region: String,
   pub key: String,
   pub secret: String,
+  pub token: Option<String>,
 }
 
 impl Configuration {
@@ -20,6 +24,24 @@ impl Configuration {
       region,
       key,
       secret,
+      token: None,
+    }
+  }
+
+  /// Like [`from_static`](Self::from_static), for temporary credentials
+  /// issued alongside a session token (e.g. from `aws sts assume-role` or
+  /// an instance role).
+  pub fn from_static_with_token(
+    region: String,
+    key: String,
+    secret: String,
+    token: String,
+  ) -> Self {
+    Self {
+      region,
+      key,
+      secret,
+      token: Some(token),
     }
   }
 
@@ -27,6 +49,7 @@ impl Configuration {
   ///   env vars (AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY) (AWS_DEFAULT_REGION)
   ///   profile (AWS_PROFILE)
   ///   default profile
+  ///   ECS/Fargate container credentials, then EC2 IMDSv2 instance profile
   pub fn auto() -> Result<Self> {
     if let Ok(c) = Self::from_env() {
       Ok(c)
@@ -34,16 +57,27 @@ impl Configuration {
       Ok(c)
     } else if let Ok(c) = Self::from_profile_static("default") {
       Ok(c)
+    } else if let Ok(c) = Self::from_instance_metadata() {
+      Ok(c.configuration)
     } else {
       Err(anyhow!("failed to find configuration automatically"))
     }
   }
 
+  /// Fetch temporary credentials from the ECS/Fargate container credentials
+  /// endpoint, falling back to the EC2 IMDSv2 instance profile endpoint.
+  /// The returned [`RefreshableConfiguration`] carries the expiry so the
+  /// caller knows when to fetch fresh credentials.
+  pub fn from_instance_metadata() -> Result<RefreshableConfiguration> {
+    providers::from_instance_metadata()
+  }
+
   pub fn from_env() -> Result<Self> {
     let c = Self {
       region: var("AWS_DEFAULT_REGION")?,
       key: var("AWS_ACCESS_KEY_ID")?,
       secret: var("AWS_SECRET_ACCESS_KEY")?,
+      token: var("AWS_SESSION_TOKEN").ok(),
     };
 
     Ok(c)
@@ -52,12 +86,13 @@ impl Configuration {
   pub fn from_profile_static(profile: &str) -> Result<Self> {
     let (cred_path, conf_path) = paths()?;
 
-    let (key, secret) = {
+    let (key, secret, token) = {
       let cred_raw = read_to_string(cred_path)?;
       let profile_line = format!("[{}]", profile);
       let mut profile_found = false;
       let mut key = None;
       let mut secret = None;
+      let mut token = None;
 
       for line in cred_raw.lines() {
         if line.starts_with("[") {
@@ -73,10 +108,11 @@ impl Configuration {
         }
 
         if profile_found {
-          let lp: Vec<&str> = line.split("=").map(|x| x.trim()).collect();
+          let lp: Vec<&str> = line.splitn(2, "=").map(|x| x.trim()).collect();
           match lp[..] {
             [CRED_KEY, val] => key = Some(val.to_owned()),
             [CRED_SECRET, val] => secret = Some(val.to_owned()),
+            [CRED_TOKEN, val] => token = Some(val.to_owned()),
             _ => {}
           }
         }
@@ -86,7 +122,7 @@ impl Configuration {
         return Err(anyhow!("profile not found in credentials"));
       }
 
-      (key, secret)
+      (key, secret, token)
     };
 
     let region = {
@@ -113,7 +149,7 @@ impl Configuration {
         }
 
         if profile_found {
-          let lp: Vec<&str> = line.split("=").map(|x| x.trim()).collect();
+          let lp: Vec<&str> = line.splitn(2, "=").map(|x| x.trim()).collect();
           match lp[..] {
             [CONF_REGION, val] => region = Some(val.to_owned()),
             _ => {}
@@ -133,6 +169,7 @@ impl Configuration {
       key: key.ok_or(anyhow!("aws_access_key_id not found for profile"))?,
       secret: secret
         .ok_or(anyhow!("aws_secret_access_key not found for profile"))?,
+      token,
     })
   }
 
@@ -167,3 +204,38 @@ fn paths() -> Result<(PathBuf, PathBuf)> {
 
   Ok((cred, config))
 }
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+  use super::*;
+  use std::env::set_var;
+  use std::fs::{create_dir_all, write};
+
+  #[test]
+  fn test_from_profile_static_reads_padded_session_token() {
+    let home = std::env::temp_dir()
+      .join(format!("awsbs-test-home-{}", std::process::id()));
+    let aws_dir = home.join(".aws");
+    create_dir_all(&aws_dir).unwrap();
+
+    write(
+      aws_dir.join("credentials"),
+      "[default]\n\
+       aws_access_key_id = AKIDEXAMPLE\n\
+       aws_secret_access_key = wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY\n\
+       aws_session_token = AQoDYXdzEJr//////////wEaDPadding+BA==\n",
+    )
+    .unwrap();
+    write(aws_dir.join("config"), "[default]\nregion = us-east-1\n").unwrap();
+
+    set_var("HOME", &home);
+
+    let conf = Configuration::from_profile_static("default").unwrap();
+
+    assert_eq!(conf.key, "AKIDEXAMPLE");
+    assert_eq!(
+      conf.token.as_deref(),
+      Some("AQoDYXdzEJr//////////wEaDPadding+BA==")
+    );
+  }
+}
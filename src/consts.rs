@@ -1,4 +1,16 @@
 pub const AMZ_DATE: &str = "x-amz-date";
+pub const AMZ_SECURITY_TOKEN: &str = "x-amz-security-token";
+pub const AMZ_CONTENT_SHA256: &str = "x-amz-content-sha256";
+pub const AMZ_DECODED_CONTENT_LENGTH: &str = "x-amz-decoded-content-length";
 pub const CT_VALUE: &str = "application/x-www-form-urlencoded; charset=utf-8";
 pub const ALGORITHM: &str = "AWS4-HMAC-SHA256";
-pub const SIGNED_HEADERS: &str = "content-type;host;x-amz-date";
+pub const STREAMING_ALGORITHM: &str = "STREAMING-AWS4-HMAC-SHA256-PAYLOAD";
+/// String-to-sign algorithm for an individual chunk of a streaming upload;
+/// distinct from [`STREAMING_ALGORITHM`], which is the canonical payload
+/// hash literal used only for the seed signature.
+pub const CHUNK_ALGORITHM: &str = "AWS4-HMAC-SHA256-PAYLOAD";
+/// SHA-256 of an empty byte string, used as the payload hash in chunk
+/// string-to-sign computations, where the chunk signature covers the
+/// chunk data itself rather than a request body.
+pub const EMPTY_SHA256: &str =
+  "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
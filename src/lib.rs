@@ -0,0 +1,13 @@
+mod configs;
+mod consts;
+mod providers;
+mod sign;
+mod verify;
+
+pub use configs::Configuration;
+pub use providers::RefreshableConfiguration;
+pub use sign::{
+  presign, sign_prepared, sign_prepared_streaming, ChunkSigner, PayloadHash,
+  SignSupported,
+};
+pub use verify::{verify_v4, SkewConfig, VerifyError};
@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::env::var;
+use std::time::Duration;
+
+use crate::Configuration;
+
+const IMDS_BASE: &str = "http://169.254.169.254";
+const IMDS_TOKEN_TTL_HEADER: &str = "x-aws-ec2-metadata-token-ttl-seconds";
+const IMDS_TOKEN_HEADER: &str = "x-aws-ec2-metadata-token";
+const IMDS_TOKEN_TTL_SECONDS: &str = "21600";
+const CONTAINER_CREDENTIALS_HOST: &str = "169.254.170.2";
+
+/// These endpoints are link-local addresses that only exist on EC2/ECS; on
+/// any other host they're either firewalled or simply unreachable, and
+/// ureq's 30s default would make `Configuration::auto()`'s fallback chain
+/// block for a long time on every non-EC2 machine. AWS's own SDKs use a
+/// similarly short timeout here for the same reason.
+const IMDS_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize)]
+struct SecurityCredentials {
+  #[serde(rename = "AccessKeyId")]
+  access_key_id: String,
+  #[serde(rename = "SecretAccessKey")]
+  secret_access_key: String,
+  #[serde(rename = "Token")]
+  token: String,
+  #[serde(rename = "Expiration")]
+  expiration: String,
+}
+
+/// A [`Configuration`] sourced from a provider that issues temporary
+/// credentials, along with when they expire so the caller knows when to
+/// fetch fresh ones.
+#[derive(Debug)]
+pub struct RefreshableConfiguration {
+  pub configuration: Configuration,
+  pub expiration: String,
+}
+
+/// Try the ECS/Fargate container credentials endpoint, then fall back to
+/// the EC2 IMDSv2 instance profile endpoint.
+pub(crate) fn from_instance_metadata() -> Result<RefreshableConfiguration> {
+  from_container_credentials().or_else(|_| from_imds())
+}
+
+fn from_container_credentials() -> Result<RefreshableConfiguration> {
+  let uri = if let Ok(uri) = var("AWS_CONTAINER_CREDENTIALS_FULL_URI") {
+    uri
+  } else if let Ok(path) = var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI") {
+    format!("http://{}{}", CONTAINER_CREDENTIALS_HOST, path)
+  } else {
+    return Err(anyhow!(
+      "neither AWS_CONTAINER_CREDENTIALS_FULL_URI nor AWS_CONTAINER_CREDENTIALS_RELATIVE_URI is set"
+    ));
+  };
+
+  let creds: SecurityCredentials = ureq::get(&uri)
+    .timeout(IMDS_TIMEOUT)
+    .call()?
+    .into_json()?;
+
+  to_configuration(creds, region(None))
+}
+
+fn from_imds() -> Result<RefreshableConfiguration> {
+  let token = ureq::put(&format!("{}/latest/api/token", IMDS_BASE))
+    .set(IMDS_TOKEN_TTL_HEADER, IMDS_TOKEN_TTL_SECONDS)
+    .timeout(IMDS_TIMEOUT)
+    .call()?
+    .into_string()?;
+
+  let role = ureq::get(&format!(
+    "{}/latest/meta-data/iam/security-credentials/",
+    IMDS_BASE
+  ))
+  .set(IMDS_TOKEN_HEADER, &token)
+  .timeout(IMDS_TIMEOUT)
+  .call()?
+  .into_string()?;
+
+  let creds: SecurityCredentials = ureq::get(&format!(
+    "{}/latest/meta-data/iam/security-credentials/{}",
+    IMDS_BASE,
+    role.trim()
+  ))
+  .set(IMDS_TOKEN_HEADER, &token)
+  .timeout(IMDS_TIMEOUT)
+  .call()?
+  .into_json()?;
+
+  to_configuration(creds, region(Some(&token)))
+}
+
+fn to_configuration(
+  creds: SecurityCredentials,
+  region: Result<String>,
+) -> Result<RefreshableConfiguration> {
+  Ok(RefreshableConfiguration {
+    configuration: Configuration::from_static_with_token(
+      region?,
+      creds.access_key_id,
+      creds.secret_access_key,
+      creds.token,
+    ),
+    expiration: creds.expiration,
+  })
+}
+
+/// Neither credential endpoint returns a region, so fall back to the usual
+/// env vars and, for EC2, the IMDS placement endpoint.
+fn region(imds_token: Option<&str>) -> Result<String> {
+  if let Ok(region) = var("AWS_REGION").or_else(|_| var("AWS_DEFAULT_REGION")) {
+    return Ok(region);
+  }
+
+  let token = imds_token
+    .ok_or_else(|| anyhow!("AWS_REGION/AWS_DEFAULT_REGION not set"))?;
+
+  Ok(
+    ureq::get(&format!("{}/latest/meta-data/placement/region", IMDS_BASE))
+      .set(IMDS_TOKEN_HEADER, token)
+      .timeout(IMDS_TIMEOUT)
+      .call()?
+      .into_string()?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_to_configuration() {
+    let creds = SecurityCredentials {
+      access_key_id: "AKIDEXAMPLE".to_owned(),
+      secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_owned(),
+      token: "EXAMPLESESSIONTOKEN".to_owned(),
+      expiration: "2020-01-01T00:00:00Z".to_owned(),
+    };
+
+    let refreshable =
+      to_configuration(creds, Ok("us-east-1".to_owned())).unwrap();
+
+    assert_eq!(refreshable.configuration.key, "AKIDEXAMPLE");
+    assert_eq!(refreshable.configuration.region, "us-east-1");
+    assert_eq!(
+      refreshable.configuration.token.as_deref(),
+      Some("EXAMPLESESSIONTOKEN")
+    );
+    assert_eq!(refreshable.expiration, "2020-01-01T00:00:00Z");
+  }
+
+  #[test]
+  fn test_region_errors_without_env_or_imds_token() {
+    // Only meaningful when the ambient environment doesn't already carry a
+    // region, which is the case in the test runner.
+    if var("AWS_REGION").is_ok() || var("AWS_DEFAULT_REGION").is_ok() {
+      return;
+    }
+
+    assert!(region(None).is_err());
+  }
+}